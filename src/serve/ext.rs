@@ -0,0 +1,42 @@
+use axum::{
+    async_trait,
+    body::{Body, HttpBody},
+    extract::FromRequest,
+    http::{HeaderMap, Method, Request, Uri},
+};
+use std::convert::Infallible;
+
+/// The subset of an incoming request that `frontend.rs`'s CGI bridge needs.
+/// `body` is left as the raw axum/hyper body stream rather than buffered
+/// into a `Vec<u8>`/`Bytes`, so large uploads are piped into the CGI
+/// process's stdin incrementally instead of sitting fully in memory first.
+pub(crate) struct RequestExt {
+    pub(crate) method: Method,
+    pub(crate) uri: Uri,
+    pub(crate) headers: HeaderMap,
+    pub(crate) body: Option<Body>,
+}
+
+#[async_trait]
+impl<S> FromRequest<S, Body> for RequestExt
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request(req: Request<Body>, _state: &S) -> Result<Self, Self::Rejection> {
+        let (parts, body) = req.into_parts();
+
+        // Requests with no body (GET, most CGI calls) shouldn't pay for a
+        // stdin-writing task at all; only keep the stream around when
+        // there's actually something to pipe.
+        let body = if body.is_end_stream() { None } else { Some(body) };
+
+        Ok(Self {
+            method: parts.method,
+            uri: parts.uri,
+            headers: parts.headers,
+            body,
+        })
+    }
+}
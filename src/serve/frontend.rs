@@ -1,5 +1,5 @@
 use super::{
-    auth::{token, CHECK_AUTH, EXP},
+    auth::{token, EXP},
     error::AppError,
     ext::RequestExt,
     ConfigExt,
@@ -28,6 +28,43 @@ use tokio_util::io::ReaderStream;
 use tower_http::trace;
 use tracing::Level;
 
+use async_compression::tokio::bufread::{DeflateEncoder, GzipEncoder};
+use axum_server::accept::Accept;
+use futures_util::TryStreamExt;
+use std::{
+    future::Future,
+    io,
+    net::SocketAddr,
+    pin::Pin,
+};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite};
+use tower_http::add_extension::AddExtension;
+
+/// Content-Type prefixes we bother compressing; binary payloads (CGI-proxied
+/// downloads, images, ...) get no benefit and just burn CPU.
+const COMPRESSIBLE_CONTENT_TYPES: &[&str] = &["text/", "application/json", "application/javascript"];
+
+/// Trade-off knob for `ServeConfig::compression_level`: cheaper CPU vs
+/// smaller bytes on the wire.
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CompressionLevel {
+    Fast,
+    #[default]
+    Default,
+    Best,
+}
+
+impl CompressionLevel {
+    fn into_async_compression_level(self) -> async_compression::Level {
+        match self {
+            CompressionLevel::Fast => async_compression::Level::Fastest,
+            CompressionLevel::Default => async_compression::Level::Default,
+            CompressionLevel::Best => async_compression::Level::Best,
+        }
+    }
+}
+
 // Access cookie
 const ACCESS_COOKIE: &'static str = "access_token";
 // Login html
@@ -35,9 +72,153 @@ const LOGIN_HTML: &str = include_str!("../static/login.html");
 
 #[derive(Deserialize)]
 struct User {
+    username: Option<String>,
     password: String,
 }
 
+/// Resolved identity behind a successful [`AuthBackend::verify`] call. Kept
+/// deliberately small for now; future per-user features (quotas, audit)
+/// should grow this rather than threading a separate lookup key.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Identity {
+    pub(crate) username: Option<String>,
+}
+
+/// A pluggable way to turn a (username, secret) pair into an [`Identity`].
+/// `auth_middleware`, `post_login` and token issuance are generic over this
+/// so multi-user/PAM/external-IdP setups don't require forking the static
+/// password check.
+#[async_trait::async_trait]
+pub(crate) trait AuthBackend: Send + Sync {
+    async fn verify(&self, username: Option<&str>, secret: &str) -> anyhow::Result<Identity>;
+
+    /// Whether this backend can ever reject a request. `false` means auth is
+    /// effectively disabled (e.g. no password configured) and callers should
+    /// let every request through without checking a token.
+    fn requires_auth(&self) -> bool {
+        true
+    }
+}
+
+/// The original behavior: a single password shared by every user, compared
+/// to `ServeConfig::auth_password`. `None` disables auth entirely.
+pub(crate) struct StaticPasswordBackend {
+    password: Option<String>,
+}
+
+impl StaticPasswordBackend {
+    pub(crate) fn new(password: Option<String>) -> Self {
+        Self { password }
+    }
+}
+
+#[async_trait::async_trait]
+impl AuthBackend for StaticPasswordBackend {
+    async fn verify(&self, username: Option<&str>, secret: &str) -> anyhow::Result<Identity> {
+        match &self.password {
+            Some(p) if p == secret => Ok(Identity {
+                username: username.map(str::to_owned),
+            }),
+            None => Ok(Identity {
+                username: username.map(str::to_owned),
+            }),
+            Some(_) => Err(anyhow::anyhow!("invalid password")),
+        }
+    }
+
+    fn requires_auth(&self) -> bool {
+        self.password.is_some()
+    }
+}
+
+/// An htpasswd file backend: `username:hash` lines, bcrypt (`$2y$`/`$2a$`/
+/// `$2b$`) or legacy `{SHA}`-prefixed SHA1. Reloads from disk whenever the
+/// file's mtime changes so rotating credentials doesn't need a restart.
+pub(crate) struct HtpasswdBackend {
+    path: std::path::PathBuf,
+    entries: tokio::sync::RwLock<std::collections::HashMap<String, String>>,
+    last_modified: tokio::sync::RwLock<Option<std::time::SystemTime>>,
+}
+
+impl HtpasswdBackend {
+    pub(crate) async fn new(path: std::path::PathBuf) -> anyhow::Result<Self> {
+        let backend = Self {
+            path,
+            entries: tokio::sync::RwLock::new(std::collections::HashMap::new()),
+            last_modified: tokio::sync::RwLock::new(None),
+        };
+        backend.reload_if_changed().await?;
+        Ok(backend)
+    }
+
+    /// Re-read the htpasswd file if its mtime moved since the last load.
+    async fn reload_if_changed(&self) -> anyhow::Result<()> {
+        let modified = file_mtime(&self.path);
+        if modified == *self.last_modified.read().await {
+            return Ok(());
+        }
+
+        let contents = tokio::fs::read_to_string(&self.path)
+            .await
+            .with_context(|| format!("reading htpasswd file {}", self.path.display()))?;
+
+        let mut entries = std::collections::HashMap::new();
+        for line in contents.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some((user, hash)) = line.split_once(':') {
+                entries.insert(user.to_owned(), hash.to_owned());
+            }
+        }
+
+        *self.entries.write().await = entries;
+        *self.last_modified.write().await = modified;
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl AuthBackend for HtpasswdBackend {
+    async fn verify(&self, username: Option<&str>, secret: &str) -> anyhow::Result<Identity> {
+        self.reload_if_changed().await?;
+
+        let username = username.context("htpasswd backend requires a username")?;
+        let entries = self.entries.read().await;
+        let hash = entries
+            .get(username)
+            .context("unknown user")?;
+
+        let ok = if let Some(digest) = hash.strip_prefix("{SHA}") {
+            use sha1::Digest;
+            use subtle::ConstantTimeEq;
+            let computed = base64::encode(sha1::Sha1::digest(secret.as_bytes()));
+            computed.as_bytes().ct_eq(digest.as_bytes()).into()
+        } else {
+            bcrypt::verify(secret, hash).unwrap_or(false)
+        };
+
+        if !ok {
+            anyhow::bail!("invalid password");
+        }
+
+        Ok(Identity {
+            username: Some(username.to_owned()),
+        })
+    }
+}
+
+/// Shared server state: the static config pair, the resolved auth backend
+/// (boxed so `auth_middleware`/`post_login` don't care which implementation
+/// is active), and the semaphore that bounds concurrent CGI processes.
+type AppState = Arc<(
+    ServeConfig,
+    InstallConfig,
+    Arc<dyn AuthBackend>,
+    Arc<tokio::sync::Semaphore>,
+)>;
+
 pub(super) struct FrontendServer(ServeConfig, InstallConfig, tokio::sync::mpsc::Receiver<()>);
 
 impl Running for FrontendServer {
@@ -59,8 +240,27 @@ impl FrontendServer {
     async fn start_server(self) -> anyhow::Result<()> {
         log::info!("Starting frontend server: {}", self.0.bind);
 
-        // Set check auth
-        CHECK_AUTH.set(self.0.auth_password.clone())?;
+        // Resolve the configured auth backend: an htpasswd file if one was
+        // given, otherwise the original single shared password.
+        let auth_backend: Arc<dyn AuthBackend> = match self.0.htpasswd_file.clone() {
+            Some(path) => Arc::new(HtpasswdBackend::new(path).await?),
+            None => Arc::new(StaticPasswordBackend::new(self.0.auth_password.clone())),
+        };
+
+        // Bounds how many CGI processes may be in flight at once, so a burst
+        // of requests can't fork-bomb the NAS.
+        let cgi_semaphore = Arc::new(tokio::sync::Semaphore::new(self.0.max_concurrent_cgi));
+
+        let state: AppState = Arc::new((
+            self.0.clone(),
+            self.1.clone(),
+            auth_backend,
+            cgi_semaphore,
+        ));
+
+        // Counts requests currently being handled, so a shutdown signal can
+        // wait for them to drain instead of cutting them off mid-flight.
+        let in_flight = Arc::new(std::sync::atomic::AtomicUsize::new(0));
 
         // router
         let router = Router::new()
@@ -68,7 +268,10 @@ impl FrontendServer {
             .route("/", any(get_pan_thunder_com))
             .route("/*path", any(get_pan_thunder_com))
             // Need to auth middleware
-            .route_layer(axum::middleware::from_fn(auth_middleware))
+            .route_layer(axum::middleware::from_fn_with_state(
+                state.clone(),
+                auth_middleware,
+            ))
             .route("/login", get(get_login))
             .route("/login", post(post_login))
             .layer(
@@ -78,7 +281,11 @@ impl FrontendServer {
                     .on_request(trace::DefaultOnRequest::new().level(Level::INFO))
                     .on_failure(trace::DefaultOnFailure::new().level(Level::WARN)),
             )
-            .with_state(Arc::new((self.0.clone(), self.1.clone())));
+            .layer(axum::middleware::from_fn_with_state(
+                in_flight.clone(),
+                track_in_flight,
+            ))
+            .with_state(state);
 
         // http server config
         let http_config = HttpConfig::new()
@@ -97,18 +304,40 @@ impl FrontendServer {
         let handle = Handle::new();
 
         // Wait for the server to shutdown gracefully
-        tokio::spawn(graceful_shutdown_signal(handle.clone(), self.2));
+        tokio::spawn(graceful_shutdown_signal(
+            handle.clone(),
+            self.2,
+            in_flight,
+            self.0.shutdown_grace_period,
+        ));
 
         // If tls_cert and tls_key is not None, use https
-        let result = match (self.0.tls_cert, self.0.tls_key) {
+        let result = match (self.0.tls_cert.clone(), self.0.tls_key.clone()) {
             (Some(cert), Some(key)) => {
                 // Load tls config
-                let tls_config = RustlsConfig::from_pem_file(cert, key).await?;
+                let tls_config = RustlsConfig::from_pem_file(&cert, &key).await?;
+
+                // Watch the cert/key files and hot-swap the in-memory config
+                // on change, so renewing a Let's Encrypt cert doesn't require
+                // restarting the whole frontend.
+                tokio::spawn(watch_tls_certs(
+                    tls_config.clone(),
+                    cert,
+                    key,
+                    self.0.tls_reload_interval,
+                ));
 
-                axum_server::bind_rustls(self.0.bind, tls_config)
+                // PROXY headers (if any) precede the TLS handshake on the
+                // wire, so the proxy-protocol acceptor must run before the
+                // rustls acceptor, not after.
+                let acceptor = axum_server::tls_rustls::RustlsAcceptor::new(tls_config)
+                    .acceptor(ProxyProtocolAcceptor { enabled: self.0.proxy_protocol });
+
+                axum_server::bind(self.0.bind)
                     .handle(handle)
                     .addr_incoming_config(incoming_config)
                     .http_config(http_config)
+                    .acceptor(acceptor)
                     .serve(router.into_make_service())
                     .await
             }
@@ -117,6 +346,7 @@ impl FrontendServer {
                     .handle(handle)
                     .addr_incoming_config(incoming_config)
                     .http_config(http_config)
+                    .acceptor(ProxyProtocolAcceptor { enabled: self.0.proxy_protocol })
                     .serve(router.into_make_service())
                     .await
             }
@@ -130,23 +360,22 @@ impl FrontendServer {
     }
 }
 
-/// Authentication
-fn authentication(auth_password: &str) -> bool {
-    match CHECK_AUTH.get() {
-        Some(Some(p)) => auth_password.eq(p),
-        _ => true,
-    }
-}
-
 /// GET /login handler
 async fn get_login() -> Html<&'static str> {
     Html(LOGIN_HTML)
 }
 
 /// POST Login handler
-async fn post_login(user: Form<User>) -> Result<impl IntoResponse, Redirect> {
-    if authentication(user.password.as_str()) {
-        if let Ok(token) = token::generate_token() {
+async fn post_login(
+    State(conf): State<AppState>,
+    user: Form<User>,
+) -> Result<impl IntoResponse, Redirect> {
+    if let Ok(identity) = conf
+        .2
+        .verify(user.username.as_deref(), user.password.as_str())
+        .await
+    {
+        if let Ok(token) = token::generate_token(&identity) {
             let resp = Response::builder()
                 .header(header::LOCATION, constant::SYNOPKG_WEB_UI_HOME)
                 .header(
@@ -170,19 +399,40 @@ async fn get_webman_login() -> Json<&'static str> {
 
 /// Any "/webman/3rdparty/pan-thunder-com/index.cgi/" handler
 async fn get_pan_thunder_com(
-    State(conf): State<Arc<(ServeConfig, InstallConfig)>>,
+    State(conf): State<AppState>,
+    axum::Extension(proxy_peer): axum::Extension<Option<ProxyPeerAddr>>,
     req: RequestExt,
 ) -> Result<impl IntoResponse, AppError> {
     if !req.uri.to_string().contains(constant::SYNOPKG_WEB_UI_HOME) {
         return Ok(Redirect::temporary(constant::SYNOPKG_WEB_UI_HOME).into_response());
     }
 
+    // Cap the number of CGI processes in flight; reject fast rather than
+    // queuing behind an unbounded number of forked children.
+    let permit = match conf.3.clone().try_acquire_owned() {
+        Ok(permit) => permit,
+        Err(_) => {
+            return Ok((
+                StatusCode::SERVICE_UNAVAILABLE,
+                [(header::RETRY_AFTER, "1")],
+                "Too many concurrent CGI requests",
+            )
+                .into_response());
+        }
+    };
+
     // environment variables
     let envs = (&conf.0, &conf.1).envs()?;
 
     // My Server real host
     let remove_host = extract_real_host(&req);
 
+    // Prefer the address a PROXY protocol header told us about (the real
+    // client, when we're behind nginx/HAProxy) over the Host header.
+    let remote_addr = proxy_peer
+        .map(|p| p.0.ip().to_string())
+        .unwrap_or_else(|| remove_host.to_string());
+
     let mut cmd = tokio::process::Command::new(constant::SYNOPKG_CLI_WEB);
     cmd.current_dir(constant::SYNOPKG_PKGDEST)
         .envs(envs)
@@ -203,7 +453,7 @@ async fn get_pan_thunder_com(
         .env("SCRIPT_NAME", ".")
         .env("SCRIPT_FILENAME", req.uri.path())
         .env("SERVER_PORT", conf.0.bind.port().to_string())
-        .env("REMOTE_ADDR", remove_host)
+        .env("REMOTE_ADDR", &remote_addr)
         .env("SERVER_NAME", remove_host)
         .uid(conf.1.uid)
         .gid(conf.1.gid)
@@ -235,16 +485,51 @@ async fn get_pan_thunder_com(
     });
 
     let mut child = cmd.spawn()?;
+    let child_pid = child.id();
 
-    if let Some(body) = req.body {
-        if let Some(w) = child.stdin.as_mut() {
-            let mut r = BufReader::new(&body[..]);
-            tokio::io::copy(&mut r, w).await?;
+    // Pipe the request body straight into the child's stdin as it arrives
+    // (rather than buffering the whole thing in memory first — important
+    // for large Thunder uploads) and then wait for it to exit. Both steps
+    // share a single deadline: a stalled client body is just as capable of
+    // pinning this task (and the forked child, and the CGI semaphore
+    // permit) forever as a hung CGI process is, so bounding only the final
+    // wait isn't enough.
+    let cgi_fut = async {
+        if let Some(body) = req.body {
+            if let Some(mut w) = child.stdin.take() {
+                let mut reader = tokio_util::io::StreamReader::new(
+                    body.map_err(|e| io::Error::new(io::ErrorKind::Other, e)),
+                );
+                tokio::io::copy(&mut reader, &mut w).await?;
+            }
         }
-    }
 
-    // Wait for the child to exit
-    let output = child.wait_with_output().await?;
+        child.wait_with_output().await
+    };
+
+    let output = tokio::select! {
+        result = cgi_fut => result?,
+        _ = tokio::time::sleep(conf.0.cgi_timeout) => {
+            log::warn!(
+                "CGI request to {} timed out after {:?}",
+                req.uri,
+                conf.0.cgi_timeout,
+            );
+            // Kill the child in the background rather than awaiting the
+            // SIGTERM/SIGKILL grace period inline: the client has already
+            // waited a full `cgi_timeout`, and making it wait out the kill
+            // too would undo the point of bounding this request at all.
+            // Move the permit along so the CGI-concurrency slot stays held
+            // until the child is actually gone.
+            if let Some(pid) = child_pid {
+                tokio::spawn(async move {
+                    terminate_cgi_process(pid).await;
+                    drop(permit);
+                });
+            }
+            return Ok((StatusCode::GATEWAY_TIMEOUT, "CGI process timed out").into_response());
+        }
+    };
 
     // Get status code
     let mut status_code = 200;
@@ -277,10 +562,398 @@ async fn get_pan_thunder_com(
         }
     }
 
-    Ok(builder
-        .status(status_code)
-        .body(StreamBody::from(ReaderStream::new(cursor)))?
-        .into_response())
+    let content_type = headers_content_type(builder.headers_ref());
+    let compressible = content_type
+        .map(|ct| COMPRESSIBLE_CONTENT_TYPES.iter().any(|p| ct.starts_with(p)))
+        .unwrap_or(false);
+    let body_len = cursor.get_ref().len() as u64 - cursor.position();
+
+    let encoding = if compressible && body_len >= conf.0.compression_min_size {
+        negotiate_encoding(&req)
+    } else {
+        None
+    };
+
+    // A streaming encoder changes the byte count on the fly, so any
+    // upstream Content-Length would be a lie; drop it in favor of chunked
+    // transfer encoding.
+    if encoding.is_some() {
+        builder = builder.header(header::CONTENT_ENCODING, encoding.unwrap().as_str());
+        if let Some(headers) = builder.headers_mut() {
+            headers.remove(header::CONTENT_LENGTH);
+        }
+    }
+
+    let body = match encoding {
+        Some(ContentEncoding::Gzip) => {
+            let level = conf.0.compression_level.into_async_compression_level();
+            let enc = GzipEncoder::with_quality(BufReader::new(cursor), level);
+            StreamBody::from(ReaderStream::new(enc))
+        }
+        Some(ContentEncoding::Deflate) => {
+            let level = conf.0.compression_level.into_async_compression_level();
+            let enc = DeflateEncoder::with_quality(BufReader::new(cursor), level);
+            StreamBody::from(ReaderStream::new(enc))
+        }
+        None => StreamBody::from(ReaderStream::new(cursor)),
+    };
+
+    Ok(builder.status(status_code).body(body)?.into_response())
+}
+
+/// Content-Encoding values we know how to produce, in preference order.
+#[derive(Clone, Copy)]
+enum ContentEncoding {
+    Gzip,
+    Deflate,
+}
+
+impl ContentEncoding {
+    fn as_str(self) -> &'static str {
+        match self {
+            ContentEncoding::Gzip => "gzip",
+            ContentEncoding::Deflate => "deflate",
+        }
+    }
+}
+
+/// Pick a compression scheme from the client's `Accept-Encoding` header,
+/// preferring gzip since it's more widely cached/understood than raw deflate.
+fn negotiate_encoding(req: &RequestExt) -> Option<ContentEncoding> {
+    let accept_encoding = req
+        .headers
+        .get(header::ACCEPT_ENCODING)?
+        .to_str()
+        .unwrap_or_default();
+
+    let accepts = |name: &str| {
+        accept_encoding
+            .split(',')
+            .map(|s| s.trim())
+            .any(|s| s == name || s.starts_with(&format!("{name};")))
+    };
+
+    if accepts("gzip") {
+        Some(ContentEncoding::Gzip)
+    } else if accepts("deflate") {
+        Some(ContentEncoding::Deflate)
+    } else {
+        None
+    }
+}
+
+/// Read the `Content-Type` header already staged on a response builder.
+fn headers_content_type(headers: Option<&header::HeaderMap>) -> Option<&str> {
+    headers?.get(header::CONTENT_TYPE)?.to_str().ok()
+}
+
+/// Real client address recovered from a PROXY protocol header, stashed as a
+/// request extension so [`get_pan_thunder_com`] can prefer it over the
+/// `Host` header when the frontend sits behind an L4 load balancer.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct ProxyPeerAddr(pub SocketAddr);
+
+/// `Accept` implementation that peels a PROXY protocol v1/v2 header off each
+/// newly-accepted connection (when `ServeConfig::proxy_protocol` is enabled)
+/// before handing the stream to hyper, so the CGI program sees the real
+/// client instead of the reverse proxy.
+#[derive(Clone, Copy, Debug, Default)]
+struct ProxyProtocolAcceptor {
+    enabled: bool,
+}
+
+impl<I, S> Accept<I, S> for ProxyProtocolAcceptor
+where
+    I: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    S: Send + 'static,
+{
+    type Stream = PrefixedStream<I>;
+    type Service = AddExtension<S, Option<ProxyPeerAddr>>;
+    #[allow(clippy::type_complexity)]
+    type Future = Pin<Box<dyn Future<Output = io::Result<(Self::Stream, Self::Service)>> + Send>>;
+
+    fn accept(&self, mut stream: I, service: S) -> Self::Future {
+        let enabled = self.enabled;
+        Box::pin(async move {
+            if !enabled {
+                let stream = PrefixedStream::new(stream, Vec::new());
+                return Ok((stream, AddExtension::new(service, None)));
+            }
+
+            let mut prefix = [0u8; 12];
+            stream.read_exact(&mut prefix).await?;
+
+            let peer = if prefix.starts_with(PROXY_V2_SIGNATURE) {
+                match parse_proxy_v2(&mut stream, &prefix).await {
+                    Ok(peer) => peer,
+                    Err(err) => {
+                        log::warn!("Rejecting connection with malformed PROXY v2 header: {err}");
+                        return Err(err);
+                    }
+                }
+            } else if prefix.starts_with(PROXY_V1_PREFIX) {
+                match parse_proxy_v1(&mut stream, &prefix).await {
+                    Ok(peer) => Some(peer),
+                    Err(err) => {
+                        log::warn!("Rejecting connection with malformed PROXY v1 header: {err}");
+                        return Err(err);
+                    }
+                }
+            } else {
+                // Not a PROXY header after all: replay the 12 bytes we
+                // already consumed so the real HTTP request behind them
+                // (e.g. the start of "GET / HTTP/1.1") isn't silently
+                // dropped.
+                let stream = PrefixedStream::new(stream, prefix.to_vec());
+                return Ok((stream, AddExtension::new(service, None)));
+            };
+
+            let stream = PrefixedStream::new(stream, Vec::new());
+            Ok((stream, AddExtension::new(service, peer)))
+        })
+    }
+}
+
+/// Wraps a freshly-accepted connection whose first few bytes were already
+/// read off the wire (to sniff for a PROXY protocol header) but turned out
+/// to belong to the application protocol, not PROXY. Reads drain `prefix`
+/// before falling through to `inner`, so hyper sees exactly the bytes the
+/// client sent; writes pass straight through. Connections with nothing to
+/// replay (PROXY disabled, or a PROXY header fully consumed) are wrapped
+/// too, with an empty `prefix`, and read straight from `inner`.
+struct PrefixedStream<I> {
+    prefix: io::Cursor<Vec<u8>>,
+    inner: I,
+}
+
+impl<I> PrefixedStream<I> {
+    fn new(inner: I, leftover: Vec<u8>) -> Self {
+        Self {
+            prefix: io::Cursor::new(leftover),
+            inner,
+        }
+    }
+}
+
+impl<I: AsyncRead + Unpin> AsyncRead for PrefixedStream<I> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> std::task::Poll<io::Result<()>> {
+        let pos = self.prefix.position() as usize;
+        let remaining = self.prefix.get_ref().len() - pos;
+        if remaining > 0 {
+            let n = remaining.min(buf.remaining());
+            let pos_end = pos + n;
+            buf.put_slice(&self.prefix.get_ref()[pos..pos_end]);
+            self.prefix.set_position(pos_end as u64);
+            return std::task::Poll::Ready(Ok(()));
+        }
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl<I: AsyncWrite + Unpin> AsyncWrite for PrefixedStream<I> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &[u8],
+    ) -> std::task::Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(
+        self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(
+        self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+const PROXY_V1_PREFIX: &[u8] = b"PROXY ";
+const PROXY_V2_SIGNATURE: &[u8] = b"\r\n\r\n\x00\r\nQUIT\n";
+
+/// Parse the binary PROXY protocol v2 header (12-byte signature already read
+/// into `prefix`, followed by version/command, family/protocol, length,
+/// then the address block). Returns `Ok(None)` for a `LOCAL` command (e.g. a
+/// load balancer's own health check), which carries no real client address
+/// by design and should be accepted, not rejected.
+async fn parse_proxy_v2<I: AsyncRead + Unpin>(
+    stream: &mut I,
+    prefix: &[u8; 12],
+) -> io::Result<Option<ProxyPeerAddr>> {
+    let mut head = [0u8; 4];
+    stream.read_exact(&mut head).await?;
+
+    let version = head[0] >> 4;
+    if version != 2 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("unsupported PROXY v2 version: {version}"),
+        ));
+    }
+
+    let command = head[0] & 0x0F;
+    let family = head[1] >> 4;
+    let len = u16::from_be_bytes([head[2], head[3]]) as usize;
+
+    let mut addr_block = vec![0u8; len];
+    stream.read_exact(&mut addr_block).await?;
+
+    let _ = prefix; // signature already validated by the caller
+
+    // LOCAL: the proxy is connecting to itself (e.g. a health check), not
+    // relaying a client. There's no address to recover; let it through.
+    if command == 0x0 {
+        return Ok(None);
+    }
+    if command != 0x1 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("unsupported PROXY v2 command: {command:#x}"),
+        ));
+    }
+
+    let peer = match family {
+        // AF_INET
+        0x1 if addr_block.len() >= 12 => {
+            let src_ip = std::net::Ipv4Addr::new(
+                addr_block[0],
+                addr_block[1],
+                addr_block[2],
+                addr_block[3],
+            );
+            let src_port = u16::from_be_bytes([addr_block[8], addr_block[9]]);
+            SocketAddr::from((src_ip, src_port))
+        }
+        // AF_INET6
+        0x2 if addr_block.len() >= 36 => {
+            let mut octets = [0u8; 16];
+            octets.copy_from_slice(&addr_block[0..16]);
+            let src_ip = std::net::Ipv6Addr::from(octets);
+            let src_port = u16::from_be_bytes([addr_block[32], addr_block[33]]);
+            SocketAddr::from((src_ip, src_port))
+        }
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unsupported PROXY v2 address family: {family:#x}"),
+            ))
+        }
+    };
+
+    Ok(Some(ProxyPeerAddr(peer)))
+}
+
+/// Parse the human-readable PROXY protocol v1 line:
+/// `PROXY TCP4 <src> <dst> <sport> <dport>\r\n` (the leading `PROXY ` is
+/// already sitting in `prefix`).
+async fn parse_proxy_v1<I: AsyncRead + Unpin>(
+    stream: &mut I,
+    prefix: &[u8; 12],
+) -> io::Result<ProxyPeerAddr> {
+    let mut line = prefix.to_vec();
+    let mut byte = [0u8; 1];
+    // A v1 header is capped at 107 bytes total; bail out rather than reading
+    // forever if a client never sends the terminating CRLF.
+    while !line.ends_with(b"\r\n") && line.len() < 107 {
+        stream.read_exact(&mut byte).await?;
+        line.push(byte[0]);
+    }
+
+    let line = String::from_utf8(line)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    let fields: Vec<&str> = line.trim_end().split(' ').collect();
+
+    // fields[0] == "PROXY", fields[1] == "TCP4"/"TCP6"/"UNKNOWN"
+    let (src_addr, src_port) = match fields.as_slice() {
+        ["PROXY", "TCP4" | "TCP6", src, _dst, sport, _dport] => (*src, *sport),
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("malformed PROXY v1 header: {line:?}"),
+            ))
+        }
+    };
+
+    let ip: std::net::IpAddr = src_addr
+        .parse()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    let port: u16 = src_port
+        .parse()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+    Ok(ProxyPeerAddr(SocketAddr::from((ip, port))))
+}
+
+/// Poll `cert`/`key` for mtime changes and reload `tls_config` in place when
+/// either one is touched (e.g. by an ACME client like certbot renewing the
+/// certificate). Runs for the lifetime of the server; errors are logged and
+/// don't stop the watch loop, since a transient read failure (renewal
+/// mid-write) shouldn't take the server down.
+async fn watch_tls_certs(
+    tls_config: RustlsConfig,
+    cert: std::path::PathBuf,
+    key: std::path::PathBuf,
+    interval: Duration,
+) {
+    // Track each file's mtime separately: the cert and key rotate together
+    // for most ACME clients, but nothing guarantees it, and either one
+    // moving on its own is still a change worth reloading for.
+    let mut last_modified = (file_mtime(&cert), file_mtime(&key));
+
+    loop {
+        tokio::time::sleep(interval).await;
+
+        let modified = (file_mtime(&cert), file_mtime(&key));
+        if modified == last_modified {
+            continue;
+        }
+        last_modified = modified;
+
+        match tls_config.reload_from_pem_file(&cert, &key).await {
+            Ok(()) => log::info!("Reloaded TLS certificate from {}", cert.display()),
+            Err(err) => log::warn!("Failed to reload TLS certificate {}: {}", cert.display(), err),
+        }
+    }
+}
+
+/// Last-modified time of a file, or `None` if it can't be stat'd.
+fn file_mtime(path: &std::path::Path) -> Option<std::time::SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Grace period between SIGTERM and SIGKILL when a CGI process has to be
+/// force-killed after timing out.
+const CGI_KILL_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+/// Send SIGTERM, then (after a short grace period) SIGKILL, to a timed-out
+/// CGI child. We no longer hold its `Child` handle by this point (it moved
+/// into the cancelled `wait_with_output` future), but that's fine: tokio's
+/// process reaper still collects the exit status for us once it dies.
+async fn terminate_cgi_process(pid: u32) {
+    let pid = pid as libc::pid_t;
+
+    // SAFETY: `pid` is the uid/gid-dropped child we just spawned for this
+    // request; sending it a signal is always sound.
+    unsafe {
+        libc::kill(pid, libc::SIGTERM);
+    }
+
+    tokio::time::sleep(CGI_KILL_GRACE_PERIOD).await;
+
+    // SAFETY: see above; harmless if the process already exited.
+    unsafe {
+        libc::kill(pid, libc::SIGKILL);
+    }
 }
 
 /// Extract real request host (bind, port)
@@ -295,11 +968,12 @@ use axum::{http::Request, middleware::Next};
 
 /// Auth middleware
 pub(crate) async fn auth_middleware<B>(
+    State(conf): State<AppState>,
     request: Request<B>,
     next: Next<B>,
 ) -> Result<Response, Redirect> {
-    // If CHECK_AUTH is None, return true
-    if let Some(None) = CHECK_AUTH.get() {
+    // If the active backend can never deny a request, skip the token check.
+    if !conf.2.requires_auth() {
         return Ok(next.run(request).await);
     }
 
@@ -331,12 +1005,56 @@ pub(crate) async fn auth_middleware<B>(
 async fn graceful_shutdown_signal(
     handle: Handle,
     mut graceful_shutdown: tokio::sync::mpsc::Receiver<()>,
+    in_flight: Arc<std::sync::atomic::AtomicUsize>,
+    grace_period: Duration,
 ) {
-    tokio::select! {
-        _ = graceful_shutdown.recv() => {
-            println!("Received signal to shutdown");
-            handle.shutdown();
-            return ;
-        }
+    if graceful_shutdown.recv().await.is_none() {
+        return;
+    }
+
+    log::info!("Received signal to shutdown, draining in-flight requests");
+
+    // Stop accepting new connections immediately; existing ones are given
+    // up to `grace_period` to finish on their own below.
+    handle.graceful_shutdown(None);
+
+    let deadline = tokio::time::Instant::now() + grace_period;
+    while in_flight.load(std::sync::atomic::Ordering::SeqCst) > 0
+        && tokio::time::Instant::now() < deadline
+    {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    let remaining = in_flight.load(std::sync::atomic::Ordering::SeqCst);
+    if remaining > 0 {
+        log::warn!(
+            "{remaining} request(s) still in flight after {:?} grace period, forcing shutdown",
+            grace_period,
+        );
+    }
+
+    handle.shutdown();
+}
+
+/// Increments a shared counter for the lifetime of each request so
+/// [`graceful_shutdown_signal`] knows how many are still in flight when a
+/// shutdown signal arrives.
+async fn track_in_flight<B>(
+    State(in_flight): State<Arc<std::sync::atomic::AtomicUsize>>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    in_flight.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+    let _guard = InFlightGuard(in_flight);
+    next.run(request).await
+}
+
+/// Decrements the in-flight counter when a request finishes, however it
+/// finishes (success, error, or the future being dropped).
+struct InFlightGuard(Arc<std::sync::atomic::AtomicUsize>);
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, std::sync::atomic::Ordering::SeqCst);
     }
 }